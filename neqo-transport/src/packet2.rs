@@ -11,10 +11,10 @@ use crate::cid::ConnectionId;
 use crate::crypto::CryptoDxState;
 use crate::{Error, Res, QUIC_VERSION};
 
-use neqo_common::{hex, qdebug, qtrace, Encoder};
+use neqo_common::{hex, qdebug, qtrace, Decoder, Encoder};
 use neqo_crypto::{aead::Aead, hkdf, random, TLS_AES_128_GCM_SHA256, TLS_VERSION_1_3};
 
-use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::iter::ExactSizeIterator;
 use std::ops::{Deref, DerefMut, Range};
 
@@ -29,6 +29,10 @@ const PACKET_BIT_FIXED_QUIC: u8 = 0x40;
 
 const SAMPLE_SIZE: usize = 16;
 
+/// The length of the Retry integrity tag.  This is the expansion of the Retry
+/// AEAD; it is a separate quantity from [`SAMPLE_SIZE`] even though both are 16.
+const RETRY_TAG_LEN: usize = 16;
+
 pub type PacketNumber = u64;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,26 +56,75 @@ impl PacketType {
             _ => panic!("shouldn't be here"),
         }
     }
+
+    /// Derive the type of a long header packet from the low two bits of the type
+    /// field.  Version Negotiation (version == 0) is handled by the caller.
+    #[must_use]
+    fn from_type_bits(t: u8) -> Self {
+        match t {
+            PACKET_TYPE_INITIAL => Self::Initial,
+            PACKET_TYPE_0RTT => Self::ZeroRtt,
+            PACKET_TYPE_HANDSHAKE => Self::Handshake,
+            PACKET_TYPE_RETRY => Self::Retry,
+            _ => unreachable!("only two bits of type are used"),
+        }
+    }
 }
 
-/// The AEAD used for Retry is fixed, so use this.
-fn make_retry_aead() -> Aead {
+/// The AEAD used for Retry depends only on the version, so build it on demand.
+/// The Retry integrity secret changed between draft versions; select the right
+/// one here so that a single code path serves both generation and verification.
+/// Because the version is caller-supplied through the public `retry` and
+/// `verify_retry` entry points, an unsupported version is reported as an error
+/// rather than asserted.
+fn make_retry_aead(version: u32) -> Res<Aead> {
     #[cfg(debug_assertions)]
     ::neqo_crypto::assert_initialized();
 
-    let secret = hkdf::import_key(
-        TLS_VERSION_1_3,
-        TLS_AES_128_GCM_SHA256,
-        &[
+    // draft-24 through draft-28 (0xff0000_18 ..= 0xff0000_1c) share this secret.
+    // Newer versions will add arms here as their secrets are imported; an
+    // unrecognized version has no known secret and is rejected below.
+    let secret = match version {
+        0xff00_0018..=0xff00_001c => &[
             0x65, 0x6e, 0x61, 0xe3, 0x36, 0xae, 0x94, 0x17, 0xf7, 0xf0, 0xed, 0xd8, 0xd7, 0x8d,
             0x46, 0x1e, 0x2a, 0xa7, 0x08, 0x4a, 0xba, 0x7a, 0x14, 0xc1, 0xe9, 0xf7, 0x26, 0xd5,
             0x57, 0x09, 0x16, 0x9a,
         ],
-    )
-    .unwrap();
-    Aead::new(TLS_VERSION_1_3, TLS_AES_128_GCM_SHA256, &secret, "quic ").unwrap()
+        _ => return Err(Error::InvalidPacket),
+    };
+    let secret = hkdf::import_key(TLS_VERSION_1_3, TLS_AES_128_GCM_SHA256, secret).unwrap();
+    Ok(Aead::new(TLS_VERSION_1_3, TLS_AES_128_GCM_SHA256, &secret, "quic ").unwrap())
+}
+
+/// Recover the full packet number from the truncated value on the wire, given
+/// the largest packet number acknowledged so far.  This is the decoding
+/// algorithm from RFC 9000 Appendix A.3.
+fn decode_pn(largest_acknowledged: PacketNumber, truncated: PacketNumber, pn_len: usize) -> PacketNumber {
+    let expected = largest_acknowledged + 1;
+    let win = 1 << (pn_len * 8);
+    let hwin = win / 2;
+    let mask = win - 1;
+    // Find the closest candidate that is congruent to the truncated value.
+    let candidate = (expected & !mask) | truncated;
+    if candidate + hwin <= expected && candidate < (1 << 62) - win {
+        candidate + win
+    } else if candidate > expected + hwin && candidate >= win {
+        candidate - win
+    } else {
+        candidate
+    }
+}
+
+/// The value of the fixed ("QUIC") bit for a new packet.  When the peer has
+/// advertised `grease_quic_bit`, the bit is cleared at random; otherwise it is
+/// always set, as required by RFC 9000.
+fn fixed_bit(grease_quic_bit: bool) -> u8 {
+    if grease_quic_bit && (random(1)[0] & 1) == 0 {
+        0
+    } else {
+        PACKET_BIT_FIXED_QUIC
+    }
 }
-thread_local!(static RETRY_AEAD: RefCell<Aead> = RefCell::new(make_retry_aead()));
 
 struct PacketBuilderoffsets {
     /// The bits of the first octet that need masking.
@@ -93,10 +146,15 @@ pub struct PacketBuilder {
 
 impl PacketBuilder {
     /// Start building a long header packet.
-    pub fn short(mut encoder: Encoder, key_phase: bool, dcid: &ConnectionId) -> Self {
+    pub fn short(
+        mut encoder: Encoder,
+        key_phase: bool,
+        dcid: &ConnectionId,
+        grease_quic_bit: bool,
+    ) -> Self {
         let header_start = encoder.len();
         // TODO(mt) randomize the spin bit
-        encoder.encode_byte(PACKET_BIT_SHORT | PACKET_BIT_FIXED_QUIC | (u8::from(key_phase) << 2));
+        encoder.encode_byte(PACKET_BIT_SHORT | fixed_bit(grease_quic_bit) | (u8::from(key_phase) << 2));
         encoder.encode(&dcid);
         Self {
             encoder,
@@ -116,12 +174,14 @@ impl PacketBuilder {
     pub fn long(
         mut encoder: Encoder,
         pt: PacketType,
+        version: u32,
         dcid: &ConnectionId,
         scid: &ConnectionId,
+        grease_quic_bit: bool,
     ) -> Self {
         let header_start = encoder.len();
-        encoder.encode_byte(PACKET_BIT_LONG | PACKET_BIT_FIXED_QUIC | pt.code() << 4);
-        encoder.encode_uint(4, QUIC_VERSION);
+        encoder.encode_byte(PACKET_BIT_LONG | fixed_bit(grease_quic_bit) | pt.code() << 4);
+        encoder.encode_uint(4, version);
         encoder.encode_vec(1, dcid);
         encoder.encode_vec(1, scid);
         Self {
@@ -221,6 +281,7 @@ impl PacketBuilder {
     /// As Retry is odd (it has to be constructed with leading bytes),
     /// this returns a Vec<u8> rather than building on an encoder.
     pub fn retry(
+        version: u32,
         dcid: &ConnectionId,
         scid: &ConnectionId,
         token: &[u8],
@@ -235,16 +296,13 @@ impl PacketBuilder {
                 | (PACKET_TYPE_RETRY << 4)
                 | (random(1)[0] & 0xf),
         );
-        encoder.encode_uint(4, QUIC_VERSION);
+        encoder.encode_uint(4, version);
         encoder.encode_vec(1, dcid);
         encoder.encode_vec(1, scid);
         encoder.encode(token);
-        let tag = RETRY_AEAD
-            .try_with(|aead| -> Res<Vec<u8>> {
-                let mut buf = vec![0; aead.borrow().expansion()];
-                Ok(aead.borrow().encrypt(0, &encoder, &[], &mut buf)?.to_vec())
-            })
-            .map_err(|_| Error::InternalError)??;
+        let aead = make_retry_aead(version)?;
+        let mut buf = vec![0; aead.expansion()];
+        let tag = aead.encrypt(0, &encoder, &[], &mut buf)?.to_vec();
         encoder.encode(&tag);
         let mut complete: Vec<u8> = encoder.into();
         Ok(complete.split_off(start))
@@ -267,6 +325,39 @@ impl PacketBuilder {
         encoder.encode(&grease[0..4]);
         encoder.into()
     }
+
+    /// Verify the integrity tag on a Retry packet and return the carried token.
+    ///
+    /// This reconstructs the Retry pseudo-packet exactly as [`retry`] does — the
+    /// original destination connection ID as a 1-byte-length-prefixed vector,
+    /// followed by all of the Retry bytes except the trailing 16-byte tag — and
+    /// uses it as the associated data when decrypting the tag.  The version is
+    /// needed because the Retry AEAD secret changed between draft versions.
+    ///
+    /// [`retry`]: PacketBuilder::retry
+    pub fn verify_retry(packet: &[u8], odcid: &ConnectionId, version: u32) -> Res<Vec<u8>> {
+        // Parse once: this both validates the structure and locates the token,
+        // which is carried in the middle of the bytes that form the pseudo-packet.
+        let (retry, _) = PublicPacket::decode(packet, 0)?;
+        if retry.packet_type() != PacketType::Retry {
+            return Err(Error::InvalidPacket);
+        }
+
+        let aead = make_retry_aead(version)?;
+        if packet.len() <= RETRY_TAG_LEN {
+            return Err(Error::InvalidPacket);
+        }
+        let (header, tag) = packet.split_at(packet.len() - RETRY_TAG_LEN);
+        let mut pseudo = Encoder::default();
+        pseudo.encode_vec(1, odcid);
+        pseudo.encode(header);
+
+        let mut plaintext = vec![0; tag.len()];
+        aead.decrypt(0, &pseudo, tag, &mut plaintext)
+            .map_err(|_| Error::InvalidPacket)?;
+
+        Ok(retry.token().to_vec())
+    }
 }
 
 impl Deref for PacketBuilder {
@@ -289,6 +380,270 @@ impl Into<Encoder> for PacketBuilder {
     }
 }
 
+/// A packet that has been received but not yet decrypted.
+///
+/// This reads the invariant part of the header (RFC 8999) without any crypto
+/// context: bit 0x80 of the first byte distinguishes the long form (with a
+/// version and both connection IDs) from the short form (a bare destination
+/// connection ID).  It records the connection IDs, version, and packet type,
+/// along with the offset of the protected payload, which is what
+/// [`PublicPacket::decrypt`] needs to remove header protection and decrypt.
+pub struct PublicPacket<'a> {
+    /// The type of packet, derived from the first byte and the version.
+    packet_type: PacketType,
+    /// The destination connection ID.
+    dcid: ConnectionId,
+    /// The source connection ID; long header packets only.
+    scid: Option<ConnectionId>,
+    /// The token carried by an Initial or Retry packet.
+    token: &'a [u8],
+    /// The version; long header packets only.
+    version: Option<u32>,
+    /// The offset of the protected payload, i.e. the start of the packet number.
+    header_len: usize,
+    /// The bytes of this packet, trimmed to exclude any coalesced packets.
+    data: &'a [u8],
+}
+
+impl<'a> PublicPacket<'a> {
+    fn opt<T>(v: Option<T>) -> Res<T> {
+        v.ok_or(Error::NoMoreData)
+    }
+
+    /// Decode the invariant header of a packet.
+    ///
+    /// Short header packets do not carry the length of their destination
+    /// connection ID on the wire, so `local_cid_len` supplies it.  On success
+    /// this returns the packet and any trailing bytes, which hold coalesced
+    /// packets from the same datagram.
+    pub fn decode(data: &'a [u8], local_cid_len: usize) -> Res<(Self, &'a [u8])> {
+        let mut decoder = Decoder::new(data);
+        let first = Self::opt(decoder.decode_byte())?;
+
+        // Only bit 0x80 (long vs short form) is consulted here.  The fixed
+        // ("QUIC") bit is intentionally not validated: a peer that negotiated
+        // `grease_quic_bit` may send it either set or clear.
+        if first & 0x80 == PACKET_BIT_SHORT {
+            // Short header: a destination connection ID, then the protected payload.
+            let dcid = ConnectionId::from(Self::opt(decoder.decode(local_cid_len))?);
+            return Ok((
+                Self {
+                    packet_type: PacketType::Short,
+                    dcid,
+                    scid: None,
+                    token: &[],
+                    version: None,
+                    header_len: decoder.offset(),
+                    data,
+                },
+                &[],
+            ));
+        }
+
+        // Long header: the version and both connection IDs are always present.
+        let version = u32::try_from(Self::opt(decoder.decode_uint(4))?).unwrap();
+        let dcid = ConnectionId::from(Self::opt(decoder.decode_vec(1))?);
+        let scid = ConnectionId::from(Self::opt(decoder.decode_vec(1))?);
+
+        if version == 0 {
+            // Version Negotiation: the supported versions run to the end of the datagram.
+            return Ok((
+                Self {
+                    packet_type: PacketType::VersionNegotiation,
+                    dcid,
+                    scid: Some(scid),
+                    token: &[],
+                    version: Some(0),
+                    header_len: decoder.offset(),
+                    data,
+                },
+                &[],
+            ));
+        }
+
+        let packet_type = PacketType::from_type_bits((first >> 4) & 3);
+        let token = if packet_type == PacketType::Initial {
+            Self::opt(decoder.decode_vvec())?
+        } else {
+            &[]
+        };
+
+        if packet_type == PacketType::Retry {
+            // Retry has no length or packet number; it runs to the end of the
+            // datagram, with the trailing 16 bytes holding the integrity tag.
+            let header_len = decoder.offset();
+            let token = if data.len() >= header_len + RETRY_TAG_LEN {
+                &data[header_len..data.len() - RETRY_TAG_LEN]
+            } else {
+                &[]
+            };
+            return Ok((
+                Self {
+                    packet_type,
+                    dcid,
+                    scid: Some(scid),
+                    token,
+                    version: Some(version),
+                    header_len,
+                    data,
+                },
+                &[],
+            ));
+        }
+
+        // Initial, 0-RTT, and Handshake all carry a length-prefixed payload.
+        let len = Self::opt(decoder.decode_varint())? as usize;
+        let header_len = decoder.offset();
+        let end = header_len + len;
+        if end > data.len() {
+            return Err(Error::NoMoreData);
+        }
+        Ok((
+            Self {
+                packet_type,
+                dcid,
+                scid: Some(scid),
+                token,
+                version: Some(version),
+                header_len,
+                data: &data[..end],
+            },
+            &data[end..],
+        ))
+    }
+
+    #[must_use]
+    pub fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    #[must_use]
+    pub fn dcid(&self) -> &ConnectionId {
+        &self.dcid
+    }
+
+    #[must_use]
+    pub fn scid(&self) -> Option<&ConnectionId> {
+        self.scid.as_ref()
+    }
+
+    #[must_use]
+    pub fn token(&self) -> &[u8] {
+        self.token
+    }
+
+    #[must_use]
+    pub fn version(&self) -> Option<u32> {
+        self.version
+    }
+
+    /// Parse the versions offered by a Version Negotiation packet.
+    ///
+    /// Greased entries (`v & 0x0f0f0f0f == 0x0a0a0a0a`, matching how
+    /// `version_negotiation` synthesizes its grease) are discarded so that the
+    /// caller sees only genuine server-supported versions.
+    pub fn supported_versions(&self) -> Res<Vec<u32>> {
+        if self.packet_type != PacketType::VersionNegotiation {
+            return Err(Error::InvalidPacket);
+        }
+        let mut decoder = Decoder::new(&self.data[self.header_len..]);
+        let mut versions = Vec::new();
+        while let Some(v) = decoder.decode_uint(4) {
+            let v = u32::try_from(v).unwrap();
+            if v & 0x0f0f_0f0f != 0x0a0a_0a0a {
+                versions.push(v);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Remove header protection and decrypt the packet (RFC 9001 §5.4.1).
+    ///
+    /// The packet number length is itself protected, so the header-protection
+    /// sample is taken at a fixed offset — four bytes past the start of the
+    /// packet number, assuming the maximum 4-byte length.  The mask unmasks the
+    /// first byte (leaving the bits outside `first_byte_mask` untouched), which
+    /// then reveals the real packet number length; that many packet number bytes
+    /// are unmasked before the header is used as associated data for the AEAD.
+    ///
+    /// Only the truncated packet number travels on the wire, so it is expanded
+    /// against `largest_acknowledged` as described in RFC 9000 Appendix A to
+    /// recover the full packet number used as the AEAD nonce.
+    pub fn decrypt(
+        &self,
+        crypto: &mut CryptoDxState,
+        largest_acknowledged: PacketNumber,
+    ) -> Res<DecryptedPacket> {
+        let sample_offset = self.header_len + 4;
+        if sample_offset + SAMPLE_SIZE > self.data.len() {
+            return Err(Error::NoMoreData);
+        }
+        let sample = &self.data[sample_offset..sample_offset + SAMPLE_SIZE];
+        let mask = crypto.compute_mask(sample)?;
+
+        // Unmask the first byte so that the packet number length can be read.
+        let first_byte_mask = if self.packet_type == PacketType::Short {
+            0x1f
+        } else {
+            0x0f
+        };
+        let mut hdr = self.data[..self.header_len].to_vec();
+        hdr[0] = self.data[0] ^ (mask[0] & first_byte_mask);
+        let pn_len = usize::from((hdr[0] & 0x3) + 1);
+        if self.header_len + pn_len > self.data.len() {
+            return Err(Error::NoMoreData);
+        }
+
+        // Unmask the truncated packet number, appending it to the header.
+        let mut truncated: PacketNumber = 0;
+        for i in 0..pn_len {
+            let b = self.data[self.header_len + i] ^ mask[1 + i];
+            truncated = (truncated << 8) | PacketNumber::from(b);
+            hdr.push(b);
+        }
+
+        // Expand the truncated packet number against the largest acknowledged.
+        let pn = decode_pn(largest_acknowledged, truncated, pn_len);
+
+        qtrace!("decrypt pn={} hdr={}", pn, hex(&hdr));
+        let ciphertext = &self.data[self.header_len + pn_len..];
+        let body = crypto.decrypt(pn, &hdr, ciphertext)?;
+        Ok(DecryptedPacket {
+            packet_type: self.packet_type,
+            pn,
+            data: body,
+        })
+    }
+}
+
+/// The result of decrypting a [`PublicPacket`].  Dereferences to the decrypted
+/// frame payload.
+pub struct DecryptedPacket {
+    packet_type: PacketType,
+    pn: PacketNumber,
+    data: Vec<u8>,
+}
+
+impl DecryptedPacket {
+    #[must_use]
+    pub fn packet_type(&self) -> PacketType {
+        self.packet_type
+    }
+
+    #[must_use]
+    pub fn pn(&self) -> PacketNumber {
+        self.pn
+    }
+}
+
+impl Deref for DecryptedPacket {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -343,8 +698,10 @@ mod tests {
         let mut builder = PacketBuilder::long(
             Encoder::new(),
             PacketType::Initial,
+            QUIC_VERSION,
             &ConnectionId::from(&[][..]),
             &ConnectionId::from(SERVER_CID),
+            false,
         );
         builder.initial_token(&[]);
         builder.pn(1, 2);
@@ -356,7 +713,7 @@ mod tests {
     #[test]
     fn build_short() {
         let mut builder =
-            PacketBuilder::short(Encoder::new(), true, &ConnectionId::from(SERVER_CID));
+            PacketBuilder::short(Encoder::new(), true, &ConnectionId::from(SERVER_CID), false);
         builder.pn(0, 1);
         builder.encode(&[0; 3]); // Enough payload for sampling.
         let packet = builder.build(&mut default_protector()).expect("build");
@@ -369,8 +726,10 @@ mod tests {
         let mut builder = PacketBuilder::long(
             Encoder::new(),
             PacketType::Handshake,
+            QUIC_VERSION,
             &ConnectionId::from(SERVER_CID),
             &ConnectionId::from(CLIENT_CID),
+            false,
         );
         builder.pn(0, 1);
         builder.encode(&[0; 3]);
@@ -378,7 +737,7 @@ mod tests {
         assert_eq!(encoder.len(), 45);
         let first = encoder.clone();
 
-        let mut builder = PacketBuilder::short(encoder, false, &ConnectionId::from(SERVER_CID));
+        let mut builder = PacketBuilder::short(encoder, false, &ConnectionId::from(SERVER_CID), false);
         builder.pn(1, 3);
         builder.encode(&[0]); // Minimal size (packet number is big enough).
         let encoder = builder.build(&mut prot).expect("build");
@@ -395,8 +754,10 @@ mod tests {
         let mut builder = PacketBuilder::long(
             Encoder::new(),
             PacketType::Initial,
+            QUIC_VERSION,
             &ConnectionId::from(&[][..]),
             &ConnectionId::from(SERVER_CID),
+            false,
         );
         builder.initial_token(&[]);
         builder.pn(1, 2);
@@ -417,6 +778,7 @@ mod tests {
 
         fixture_init();
         let retry = PacketBuilder::retry(
+            QUIC_VERSION,
             &ConnectionId::from(&[][..]),
             &ConnectionId::from(SERVER_CID),
             b"token",
@@ -436,6 +798,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn verify_retry() {
+        fixture_init();
+        let retry = PacketBuilder::retry(
+            QUIC_VERSION,
+            &ConnectionId::from(&[][..]),
+            &ConnectionId::from(SERVER_CID),
+            b"token",
+            &ConnectionId::from(CLIENT_CID),
+        )
+        .unwrap();
+
+        let token =
+            PacketBuilder::verify_retry(&retry, &ConnectionId::from(CLIENT_CID), QUIC_VERSION)
+                .expect("verify");
+        assert_eq!(&token, b"token");
+
+        // The wrong original DCID must fail the integrity check.
+        assert!(
+            PacketBuilder::verify_retry(&retry, &ConnectionId::from(SERVER_CID), QUIC_VERSION)
+                .is_err()
+        );
+    }
+
     #[test]
     fn build_retry_multiple() {
         // Run the build_retry test a few times.
@@ -466,4 +852,128 @@ mod tests {
         }
         assert_eq!(&vn, &EXPECTED);
     }
+
+    /// A protector for the opposite direction of `default_protector`, so that
+    /// a packet built with one can be decrypted with the other.
+    fn default_deprotector() -> CryptoDxState {
+        fixture_init();
+        CryptoDxState::new_initial(CryptoDxDirection::Read, "server in", CLIENT_CID)
+    }
+
+    #[test]
+    fn decrypt_short() {
+        let mut builder =
+            PacketBuilder::short(Encoder::new(), false, &ConnectionId::from(CLIENT_CID), false);
+        builder.pn(0, 1);
+        builder.encode(&[1, 2, 3]);
+        let packet = builder.build(&mut default_protector()).expect("build");
+
+        let (public, _) =
+            PublicPacket::decode(&packet[..], CLIENT_CID.len()).expect("decode");
+        let decrypted = public.decrypt(&mut default_deprotector(), 0).expect("decrypt");
+        assert_eq!(decrypted.packet_type(), PacketType::Short);
+        assert_eq!(decrypted.pn(), 0);
+        assert_eq!(&decrypted[..], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn decrypt_long() {
+        let mut builder = PacketBuilder::long(
+            Encoder::new(),
+            PacketType::Handshake,
+            QUIC_VERSION,
+            &ConnectionId::from(SERVER_CID),
+            &ConnectionId::from(CLIENT_CID),
+            false,
+        );
+        builder.pn(0, 2);
+        builder.encode(&[4, 5, 6, 7]);
+        let packet = builder.build(&mut default_protector()).expect("build");
+
+        let (public, _) = PublicPacket::decode(&packet[..], 0).expect("decode");
+        let decrypted = public.decrypt(&mut default_deprotector(), 0).expect("decrypt");
+        assert_eq!(decrypted.packet_type(), PacketType::Handshake);
+        assert_eq!(decrypted.pn(), 0);
+        assert_eq!(&decrypted[..], &[4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn decode_grease_quic_bit() {
+        let mut builder =
+            PacketBuilder::short(Encoder::new(), false, &ConnectionId::from(SERVER_CID), false);
+        builder.pn(0, 1);
+        builder.encode(&[0; 3]);
+        let mut packet: Vec<u8> = builder.build(&mut default_protector()).expect("build").into();
+        // A peer that negotiated grease_quic_bit may clear the fixed bit.
+        packet[0] &= !PACKET_BIT_FIXED_QUIC;
+        let (decoded, _) = PublicPacket::decode(&packet, SERVER_CID.len())
+            .expect("decode tolerates a cleared fixed bit");
+        assert_eq!(decoded.packet_type(), PacketType::Short);
+    }
+
+    #[test]
+    fn decode_vn() {
+        fixture_init();
+        let vn = PacketBuilder::version_negotiation(
+            &ConnectionId::from(SERVER_CID),
+            &ConnectionId::from(CLIENT_CID),
+        );
+        let (packet, remainder) = PublicPacket::decode(&vn, 0).expect("decode VN");
+        assert_eq!(packet.packet_type(), PacketType::VersionNegotiation);
+        assert_eq!(packet.version(), Some(0));
+        assert_eq!(packet.dcid(), &ConnectionId::from(SERVER_CID));
+        assert_eq!(packet.scid(), Some(&ConnectionId::from(CLIENT_CID)));
+        assert!(remainder.is_empty());
+    }
+
+    #[test]
+    fn vn_supported_versions() {
+        fixture_init();
+        let vn = PacketBuilder::version_negotiation(
+            &ConnectionId::from(SERVER_CID),
+            &ConnectionId::from(CLIENT_CID),
+        );
+        let (packet, _) = PublicPacket::decode(&vn, 0).expect("decode VN");
+        // The greased version is discarded, leaving only the real offer.
+        assert_eq!(packet.supported_versions().unwrap(), vec![QUIC_VERSION]);
+    }
+
+    #[test]
+    fn decode_short() {
+        let mut builder =
+            PacketBuilder::short(Encoder::new(), true, &ConnectionId::from(SERVER_CID), false);
+        builder.pn(0, 1);
+        builder.encode(&[0; 3]);
+        let packet = builder.build(&mut default_protector()).expect("build");
+        let (decoded, remainder) =
+            PublicPacket::decode(&packet[..], SERVER_CID.len()).expect("decode short");
+        assert_eq!(decoded.packet_type(), PacketType::Short);
+        assert_eq!(decoded.dcid(), &ConnectionId::from(SERVER_CID));
+        assert_eq!(decoded.scid(), None);
+        assert_eq!(decoded.version(), None);
+        assert!(remainder.is_empty());
+    }
+
+    #[test]
+    fn decode_long() {
+        let mut prot = default_protector();
+        let mut builder = PacketBuilder::long(
+            Encoder::new(),
+            PacketType::Handshake,
+            QUIC_VERSION,
+            &ConnectionId::from(SERVER_CID),
+            &ConnectionId::from(CLIENT_CID),
+            false,
+        );
+        builder.pn(0, 1);
+        builder.encode(&[0; 3]);
+        let packet = builder.build(&mut prot).expect("build");
+        let (decoded, remainder) =
+            PublicPacket::decode(&packet[..], 0).expect("decode long");
+        assert_eq!(decoded.packet_type(), PacketType::Handshake);
+        assert_eq!(decoded.dcid(), &ConnectionId::from(SERVER_CID));
+        assert_eq!(decoded.scid(), Some(&ConnectionId::from(CLIENT_CID)));
+        assert_eq!(decoded.version(), Some(QUIC_VERSION));
+        assert!(remainder.is_empty());
+    }
 }
\ No newline at end of file